@@ -25,12 +25,15 @@
 //! - Colorize backtraces to be easier on the eyes
 //! - Show source snippets if source files are found on disk
 //! - Hide all the frames after the panic was already initiated
-use console::style;
+//! - Format backtraces on demand with `Settings::format_backtrace` or
+//!   `Settings::print_backtrace_to`, not just on panic
 use std::borrow::Cow;
+use std::fmt;
 use std::fs::File;
 use std::io::{self, BufRead, BufReader, ErrorKind, Write};
 use std::panic::PanicInfo;
 use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Defines how verbose the backtrace is supposed to be.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -46,8 +49,25 @@ pub enum Verbosity {
 impl Verbosity {
     /// Get the verbosity level from the `RUST_BACKTRACE` env variable.
     pub fn from_env() -> Self {
-        match std::env::var("RUST_BACKTRACE") {
+        Self::from_var(std::env::var("RUST_BACKTRACE"))
+    }
+
+    /// Get the verbosity level for library code.
+    ///
+    /// This reads `RUST_LIB_BACKTRACE` first and falls back to
+    /// `RUST_BACKTRACE`, mirroring the convention the standard library uses
+    /// to let applications keep verbose panic traces while suppressing
+    /// verbose traces from library-originated reports.
+    pub fn lib_from_env() -> Self {
+        Self::from_var(
+            std::env::var("RUST_LIB_BACKTRACE").or_else(|_| std::env::var("RUST_BACKTRACE")),
+        )
+    }
+
+    fn from_var(var: Result<String, std::env::VarError>) -> Self {
+        match var {
             Ok(ref x) if x == "full" => Verbosity::Full,
+            Ok(ref x) if x == "0" || x.eq_ignore_ascii_case("no") => Verbosity::Minimal,
             Ok(_) => Verbosity::Medium,
             Err(_) => Verbosity::Minimal,
         }
@@ -77,10 +97,18 @@ pub fn debug_install() {
     Settings::debug().install()
 }
 
-struct Frame {
-    name: Option<String>,
-    lineno: Option<u32>,
-    filename: Option<PathBuf>,
+/// A single entry of a backtrace.
+///
+/// This is exposed so that a [`Settings::add_frame_filter`] closure can
+/// inspect and classify frames the same way the built-in heuristics do.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    /// The symbol name of the frame, if it could be resolved.
+    pub name: Option<String>,
+    /// The line number the frame points to, if known.
+    pub lineno: Option<u32>,
+    /// The source file the frame points to, if known.
+    pub filename: Option<PathBuf>,
 }
 
 impl Frame {
@@ -96,7 +124,9 @@ impl Frame {
         }
     }
 
-    fn is_dependency_code(&self) -> bool {
+    /// Is this frame likely to originate from a dependency or the
+    /// language runtime, as opposed to the user's own code?
+    pub fn is_dependency_code(&self) -> bool {
         const SYM_PREFIXES: &[&str] = &[
             "std::",
             "core::",
@@ -147,13 +177,12 @@ impl Frame {
         false
     }
 
-    // Heuristically determine whether a frame is likely to be a post panic
-    // frame.
-    //
-    // Post panic frames are frames of a functions called after the actual panic
-    // is already in progress and don't contain any useful information for a
-    // reader of the backtrace.
-    fn is_post_panic_code(&self) -> bool {
+    /// Is this frame likely to be a post panic frame?
+    ///
+    /// Post panic frames are frames of a functions called after the actual
+    /// panic is already in progress and don't contain any useful information
+    /// for a reader of the backtrace.
+    pub fn is_post_panic_code(&self) -> bool {
         const SYM_PREFIXES: &[&str] = &[
             "_rust_begin_unwind",
             "panic_bounds_check",
@@ -182,9 +211,9 @@ impl Frame {
         }
     }
 
-    // Heuristically determine whether a frame is likely to be part of language
-    // runtime.
-    fn is_runtime_init_code(&self) -> bool {
+    /// Is this frame likely to be part of the language runtime's startup
+    /// code?
+    pub fn is_runtime_init_code(&self) -> bool {
         const SYM_PREFIXES: &[&str] =
             &["std::rt::lang_start::", "test::run_test::run_test_inner::"];
 
@@ -209,7 +238,7 @@ impl Frame {
     }
 
     /// Is this a call once frame?
-    fn is_call_once(&self) -> bool {
+    pub fn is_call_once(&self) -> bool {
         if let Some(name) = self.name_without_hash() {
             name.ends_with("FnOnce::call_once")
         } else {
@@ -217,28 +246,32 @@ impl Frame {
         }
     }
 
-    fn print_source(&self, s: &Settings) -> Result<(), io::Error> {
+    fn print_source(&self, w: &mut impl Write, s: &Settings) -> Result<(), io::Error> {
         let (lineno, filename) = match (self.lineno, self.filename.as_ref()) {
             (Some(a), Some(b)) => (a, b),
             // Without a line number and file name, we can't sensibly proceed.
             _ => return Ok(()),
         };
 
-        print_source(filename, lineno, s)
+        print_source(w, filename, lineno, s)
     }
 
-    fn print(&self, s: &Settings) -> Result<(), io::Error> {
+    fn print(
+        &self,
+        w: &mut impl Write,
+        s: &Settings,
+        verbosity: Verbosity,
+    ) -> Result<(), io::Error> {
         let is_dependency_code = self.is_dependency_code();
 
         let name = self.name_without_hash().unwrap_or("<unknown>");
 
         // Print function name.
-        let mut name_style = console::Style::new();
-        if is_dependency_code {
-            name_style = name_style.cyan();
+        let name_style = if is_dependency_code {
+            &s.color_scheme.dependency_code
         } else {
-            name_style = name_style.green();
-        }
+            &s.color_scheme.user_code
+        };
 
         // Print source location, if known.
         let file = match &self.filename {
@@ -248,51 +281,159 @@ impl Frame {
 
         if s.lineno_suffix {
             writeln!(
-                &s.out,
+                w,
                 "  File \"{}:{}\", in {}",
-                style(file).underlined(),
-                style(self.lineno.unwrap_or(0)).yellow(),
+                s.color_scheme.filename.apply_to(file),
+                s.color_scheme.lineno.apply_to(self.lineno.unwrap_or(0)),
                 name_style.apply_to(name)
             )?;
         } else {
             writeln!(
-                &s.out,
+                w,
                 "  File \"{}\", line {}, in {}",
-                style(file).underlined(),
-                style(self.lineno.unwrap_or(0)).yellow(),
+                s.color_scheme.filename.apply_to(file),
+                s.color_scheme.lineno.apply_to(self.lineno.unwrap_or(0)),
                 name_style.apply_to(name)
             )?;
         }
 
         // Maybe print source.
-        if s.verbosity >= Verbosity::Full {
-            self.print_source(s)?;
+        if verbosity >= Verbosity::Full {
+            self.print_source(w, s)?;
         }
 
         Ok(())
     }
 }
 
-/// Configuration for panic printing.
+/// A closure that can drop frames from the collected backtrace before it is
+/// printed.  See [`Settings::add_frame_filter`].
+pub type FrameFilter = dyn Fn(&mut Vec<&Frame>) + Send + Sync + 'static;
+
+/// The sink the installed panic handler writes its pretty output to.
+///
+/// Defaults to `Term::stderr()`; override it with [`Settings::out`] to
+/// redirect the installed hook to a log file, an in-memory buffer, or any
+/// other `io::Write`.
+#[derive(Clone)]
+enum OutputSink {
+    Term(console::Term),
+    Writer(std::sync::Arc<std::sync::Mutex<dyn Write + Send>>),
+}
+
+impl fmt::Debug for OutputSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputSink::Term(term) => f.debug_tuple("Term").field(term).finish(),
+            OutputSink::Writer(_) => f.debug_tuple("Writer").field(&"..").finish(),
+        }
+    }
+}
+
+impl Write for &OutputSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match *self {
+            OutputSink::Term(ref term) => {
+                let mut term = term;
+                term.write(buf)
+            }
+            OutputSink::Writer(ref writer) => writer.lock().unwrap().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            OutputSink::Term(ref term) => term.flush(),
+            OutputSink::Writer(ref writer) => writer.lock().unwrap().flush(),
+        }
+    }
+}
+
+/// The colors used to render the various parts of a backtrace.
+///
+/// Construct one with `ColorScheme::default()` and override the fields you
+/// care about, then pass it to [`Settings::color_scheme`] to adapt to
+/// light/dark terminals or to disable colors entirely.
 #[derive(Debug, Clone)]
+pub struct ColorScheme {
+    /// Style for frames that belong to the user's own code.
+    pub user_code: console::Style,
+    /// Style for frames that belong to dependencies or the runtime.
+    pub dependency_code: console::Style,
+    /// Style for line numbers.
+    pub lineno: console::Style,
+    /// Style for file names.
+    pub filename: console::Style,
+    /// Style for the panic message/payload.
+    pub panic_message: console::Style,
+    /// Style for source snippets.
+    pub source_line: console::Style,
+    /// Style for section headers such as "Backtrace (most recent call first):".
+    pub header: console::Style,
+}
+
+impl Default for ColorScheme {
+    fn default() -> Self {
+        Self {
+            user_code: console::Style::new().green(),
+            dependency_code: console::Style::new().cyan(),
+            lineno: console::Style::new().yellow(),
+            filename: console::Style::new().underlined(),
+            panic_message: console::Style::new().yellow(),
+            source_line: console::Style::new().dim(),
+            header: console::Style::new().bold(),
+        }
+    }
+}
+
+/// Configuration for panic printing.
+#[derive(Clone)]
 pub struct Settings {
     message: String,
-    out: console::Term,
+    out: OutputSink,
     verbosity: Verbosity,
+    lib_verbosity: Verbosity,
+    is_panic_handler: bool,
     backtrace_first: bool,
     most_recent_first: bool,
     lineno_suffix: bool,
+    frame_filters: Vec<std::sync::Arc<FrameFilter>>,
+    color_scheme: ColorScheme,
+    report_path: Option<PathBuf>,
+}
+
+impl fmt::Debug for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Settings")
+            .field("message", &self.message)
+            .field("out", &self.out)
+            .field("verbosity", &self.verbosity)
+            .field("lib_verbosity", &self.lib_verbosity)
+            .field("is_panic_handler", &self.is_panic_handler)
+            .field("backtrace_first", &self.backtrace_first)
+            .field("most_recent_first", &self.most_recent_first)
+            .field("lineno_suffix", &self.lineno_suffix)
+            .field("frame_filters", &self.frame_filters.len())
+            .field("color_scheme", &self.color_scheme)
+            .field("report_path", &self.report_path)
+            .finish()
+    }
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Self {
             verbosity: Verbosity::from_env(),
+            lib_verbosity: Verbosity::lib_from_env(),
+            is_panic_handler: true,
             message: "The application panicked (crashed).".to_owned(),
-            out: console::Term::stderr(),
+            out: OutputSink::Term(console::Term::stderr()),
             backtrace_first: true,
             most_recent_first: true,
             lineno_suffix: false,
+            frame_filters: Vec::new(),
+            color_scheme: ColorScheme::default(),
+            report_path: None,
         }
     }
 }
@@ -328,6 +469,16 @@ impl Settings {
         self
     }
 
+    /// Controls where the installed panic handler writes its output.
+    ///
+    /// Defaults to `Term::stderr()`. Use this to redirect the pretty panic
+    /// output to a log file or any other `io::Write` instead of the
+    /// terminal.
+    pub fn out(mut self, writer: impl Write + Send + 'static) -> Self {
+        self.out = OutputSink::Writer(std::sync::Arc::new(std::sync::Mutex::new(writer)));
+        self
+    }
+
     /// Controls the verbosity level.
     ///
     /// Defaults to `Verbosity::get_env()`.
@@ -336,6 +487,27 @@ impl Settings {
         self
     }
 
+    /// Controls the verbosity level used when not acting as a panic handler.
+    ///
+    /// Defaults to `Verbosity::lib_from_env()`.  This is consulted instead
+    /// of `verbosity` when `is_panic_handler` is `false`; see
+    /// [`Verbosity::lib_from_env`] for why a separate level exists.
+    pub fn lib_verbosity(mut self, v: Verbosity) -> Self {
+        self.lib_verbosity = v;
+        self
+    }
+
+    /// Controls whether these settings are used by an installed panic
+    /// handler or by a library formatting a backtrace on demand.
+    ///
+    /// Defaults to `true`.  When `true`, `verbosity` determines how much
+    /// detail is printed; when `false`, `lib_verbosity` is used instead —
+    /// see [`Verbosity::lib_from_env`] for why that distinction matters.
+    pub fn is_panic_handler(mut self, value: bool) -> Self {
+        self.is_panic_handler = value;
+        self
+    }
+
     /// Controls the backtrace position.
     ///
     /// Defaults to `true` which causes the backtrace to be printed above
@@ -365,10 +537,48 @@ impl Settings {
         self
     }
 
+    /// Registers a frame filter.
+    ///
+    /// Filters are run, in registration order, over the already cut-off
+    /// slice of frames right before they are printed.  Each filter can
+    /// inspect [`Frame`] (including its classification helpers like
+    /// [`Frame::is_dependency_code`]) and remove entries from the `Vec` it
+    /// is given, for example to drop frames from a user's own logging
+    /// crate or to collapse runs of dependency frames.
+    pub fn add_frame_filter(mut self, filter: Box<FrameFilter>) -> Self {
+        self.frame_filters.push(std::sync::Arc::from(filter));
+        self
+    }
+
+    /// Controls the colors used to render the backtrace.
+    ///
+    /// Defaults to `ColorScheme::default()`.
+    pub fn color_scheme(mut self, scheme: ColorScheme) -> Self {
+        self.color_scheme = scheme;
+        self
+    }
+
+    /// Writes a machine-readable JSON crash report to `path` in addition to
+    /// the human-readable output.
+    ///
+    /// The report contains the panic message, thread name, panic location,
+    /// target OS and CPU architecture (the compile target, not a runtime OS
+    /// version), a millisecond timestamp, and the filtered backtrace
+    /// frames. This gives applications a stable artifact they can upload to
+    /// a crash collector, mirroring how editors capture panics for later
+    /// diagnosis.
+    ///
+    /// Defaults to `None`, which disables report generation.
+    pub fn report_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.report_path = Some(path.into());
+        self
+    }
+
     /// Consumes the settings and creates a panic handler.
     pub fn create_panic_handler(self) -> Box<dyn Fn(&PanicInfo<'_>) + 'static + Sync + Send> {
         Box::new(move |pi| {
-            print_panic_and_backtrace(pi, &self).unwrap();
+            let mut out = &self.out;
+            print_panic_and_backtrace(&mut out, pi, &self).unwrap();
         })
     }
 
@@ -377,9 +587,47 @@ impl Settings {
         self.verbosity.apply_to_process();
         std::panic::set_hook(self.create_panic_handler())
     }
+
+    /// Formats a backtrace the same way the panic handler would, without
+    /// requiring an active panic.
+    ///
+    /// This reuses the same frame-collection, cutoff and source-snippet
+    /// logic as the installed panic handler, so a backtrace captured from
+    /// an error type or a log call looks exactly like one printed on panic.
+    pub fn format_backtrace(&self, bt: &backtrace::Backtrace) -> String {
+        let mut buf = Vec::new();
+        // Writing into a `Vec<u8>` cannot fail; an error opening a source
+        // file for a snippet ends the output at that frame.
+        let _ = self.print_backtrace_to(&mut buf, bt);
+        String::from_utf8_lossy(&buf).into_owned()
+    }
+
+    /// Writes a backtrace to `w`, reusing the same frame-collection, cutoff
+    /// and source-snippet logic as the installed panic handler.
+    ///
+    /// This lets a library render the same pretty traces into logs, error
+    /// types, or files, not just on panic.
+    pub fn print_backtrace_to(
+        &self,
+        w: &mut impl Write,
+        bt: &backtrace::Backtrace,
+    ) -> Result<(), io::Error> {
+        let verbosity = if self.is_panic_handler {
+            self.verbosity
+        } else {
+            self.lib_verbosity
+        };
+        print_backtrace(w, Some(bt), self, verbosity)?;
+        Ok(())
+    }
 }
 
-fn print_source(filename: &Path, lineno: u32, s: &Settings) -> Result<(), io::Error> {
+fn print_source(
+    w: &mut impl Write,
+    filename: &Path,
+    lineno: u32,
+    s: &Settings,
+) -> Result<(), io::Error> {
     let file = match File::open(filename) {
         Ok(file) => file,
         Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(()),
@@ -389,28 +637,24 @@ fn print_source(filename: &Path, lineno: u32, s: &Settings) -> Result<(), io::Er
     let reader = BufReader::new(file);
     let source_line = reader.lines().nth((lineno - 1) as usize);
     if let Some(Ok(source_line)) = source_line {
-        writeln!(&s.out, "    {}", style(source_line.trim()).dim())?;
+        writeln!(
+            w,
+            "    {}",
+            s.color_scheme.source_line.apply_to(source_line.trim())
+        )?;
     }
 
     Ok(())
 }
 
-fn print_backtrace(bt: Option<&backtrace::Backtrace>, s: &Settings) -> Result<(), io::Error> {
-    if s.most_recent_first {
-        writeln!(
-            &s.out,
-            "{}",
-            style("Backtrace (most recent call first):").bold()
-        )?;
-    } else {
-        writeln!(
-            &s.out,
-            "{}",
-            style("Backtrace (most recent call last):").bold()
-        )?;
-    }
-
-    // Collect frame info.
+/// Collects the backtrace frames that are worth showing to a reader.
+///
+/// This resolves symbols (either from `bt`, or by capturing the current
+/// stack when `bt` is `None`), cuts off the post-panic and runtime-init
+/// frames, and runs the registered [`Settings::add_frame_filter`] closures
+/// over what remains. Used both for the human-readable backtrace and for
+/// the JSON crash report.
+fn collect_frames(bt: Option<&backtrace::Backtrace>, s: &Settings) -> Vec<Frame> {
     let mut frames = Vec::new();
     if let Some(bt) = bt {
         for frame in bt.frames() {
@@ -451,39 +695,211 @@ fn print_backtrace(bt: Option<&backtrace::Backtrace>, s: &Settings) -> Result<()
         .map(|x| x - 1)
         .unwrap_or_else(|| frames.len());
 
-    // Turn them into `Frame` objects and print them.
     let mut frames = &frames[top_cutoff..bottom_cutoff];
 
     if !frames.is_empty() && frames[frames.len() - 1].is_call_once() {
         frames = &frames[..frames.len() - 1];
     }
 
+    // Let the user customize which frames are shown.
+    let mut frames: Vec<&Frame> = frames.iter().collect();
+    for filter in &s.frame_filters {
+        filter(&mut frames);
+    }
+
+    frames.into_iter().cloned().collect()
+}
+
+fn print_backtrace(
+    w: &mut impl Write,
+    bt: Option<&backtrace::Backtrace>,
+    s: &Settings,
+    verbosity: Verbosity,
+) -> Result<Vec<Frame>, io::Error> {
     if s.most_recent_first {
-        for frame in frames {
-            frame.print(s)?;
+        writeln!(
+            w,
+            "{}",
+            s.color_scheme
+                .header
+                .apply_to("Backtrace (most recent call first):")
+        )?;
+    } else {
+        writeln!(
+            w,
+            "{}",
+            s.color_scheme
+                .header
+                .apply_to("Backtrace (most recent call last):")
+        )?;
+    }
+
+    let frames = collect_frames(bt, s);
+
+    if s.most_recent_first {
+        for frame in &frames {
+            frame.print(w, s, verbosity)?;
         }
     } else {
         for frame in frames.iter().rev() {
-            frame.print(s)?;
+            frame.print(w, s, verbosity)?;
         }
     }
 
-    Ok(())
+    Ok(frames)
 }
 
-fn print_panic_and_backtrace(pi: &PanicInfo, s: &Settings) -> Result<(), io::Error> {
+/// Extracts the human-readable panic message from a [`PanicInfo`].
+///
+/// Panics carry their payload as `Box<dyn Any>`; in practice that's almost
+/// always a `String` or `&str`, so this covers both and falls back to a
+/// generic label for anything else.
+fn panic_payload<'a>(pi: &'a PanicInfo<'_>) -> &'a str {
+    pi.payload()
+        .downcast_ref::<String>()
+        .map(String::as_str)
+        .or_else(|| pi.payload().downcast_ref::<&str>().cloned())
+        .unwrap_or("Box<Any>")
+}
+
+fn print_panic_and_backtrace(
+    w: &mut impl Write,
+    pi: &PanicInfo,
+    s: &Settings,
+) -> Result<(), io::Error> {
+    let mut frames = None;
     if s.backtrace_first {
-        print_backtrace_info(s)?;
-        writeln!(&s.out)?;
+        frames = print_backtrace_info(w, s)?;
+        writeln!(w)?;
     }
-    print_panic_info(pi, s)?;
+    print_panic_info(w, pi, s)?;
     if !s.backtrace_first {
-        writeln!(&s.out)?;
-        print_backtrace_info(s)?;
+        writeln!(w)?;
+        frames = print_backtrace_info(w, s)?;
+    }
+    if let Err(err) = write_panic_report(pi, s, frames) {
+        eprintln!("better-panic: failed to write crash report: {}", err);
     }
     Ok(())
 }
 
+fn json_escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn json_string(value: &str) -> String {
+    format!("\"{}\"", json_escape(value))
+}
+
+fn json_string_or_null(value: Option<&str>) -> String {
+    match value {
+        Some(value) => json_string(value),
+        None => "null".to_owned(),
+    }
+}
+
+fn json_u32_or_null(value: Option<u32>) -> String {
+    match value {
+        Some(value) => value.to_string(),
+        None => "null".to_owned(),
+    }
+}
+
+/// Writes the JSON crash report configured via `Settings::report_path`, if
+/// any.
+///
+/// `frames`, when given, are the already-collected and filtered frames from
+/// the human-readable backtrace that was just printed for this panic; this
+/// lets the report reuse them instead of re-walking and re-resolving the
+/// native stack. When `None` (the backtrace section was suppressed by
+/// `verbosity`), the frames are collected here instead.
+fn write_panic_report(
+    pi: &PanicInfo,
+    s: &Settings,
+    frames: Option<Vec<Frame>>,
+) -> Result<(), io::Error> {
+    let path = match &s.report_path {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+
+    let thread = std::thread::current();
+    let thread_name = thread.name().unwrap_or("<unnamed>");
+
+    let payload = panic_payload(pi);
+
+    let (file, line) = match pi.location() {
+        Some(loc) => (Some(loc.file().to_owned()), Some(loc.line())),
+        None => (None, None),
+    };
+
+    let timestamp_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+
+    let frames = frames.unwrap_or_else(|| collect_frames(None, s));
+
+    let mut json = String::new();
+    json.push_str("{\n");
+    json.push_str(&format!("  \"message\": {},\n", json_string(payload)));
+    json.push_str(&format!(
+        "  \"thread_name\": {},\n",
+        json_string(thread_name)
+    ));
+    json.push_str(&format!(
+        "  \"file\": {},\n",
+        json_string_or_null(file.as_deref())
+    ));
+    json.push_str(&format!("  \"line\": {},\n", json_u32_or_null(line)));
+    json.push_str(&format!(
+        "  \"target_os\": {},\n",
+        json_string(std::env::consts::OS)
+    ));
+    json.push_str(&format!(
+        "  \"target_arch\": {},\n",
+        json_string(std::env::consts::ARCH)
+    ));
+    json.push_str(&format!("  \"timestamp_ms\": {},\n", timestamp_ms));
+    json.push_str("  \"frames\": [\n");
+    for (i, frame) in frames.iter().enumerate() {
+        json.push_str("    {\n");
+        json.push_str(&format!(
+            "      \"name\": {},\n",
+            json_string_or_null(frame.name.as_deref())
+        ));
+        json.push_str(&format!(
+            "      \"filename\": {},\n",
+            json_string_or_null(frame.filename.as_ref().and_then(|f| f.to_str()))
+        ));
+        json.push_str(&format!(
+            "      \"lineno\": {}\n",
+            json_u32_or_null(frame.lineno)
+        ));
+        json.push_str(if i + 1 == frames.len() {
+            "    }\n"
+        } else {
+            "    },\n"
+        });
+    }
+    json.push_str("  ]\n");
+    json.push_str("}\n");
+
+    std::fs::write(path, json)
+}
+
 fn trim_filename(file: &Path) -> Cow<'_, str> {
     let filename = file.to_str().unwrap_or("<bad utf8>");
     if filename.starts_with("/rustc/") {
@@ -503,72 +919,182 @@ fn trim_filename(file: &Path) -> Cow<'_, str> {
     }
 }
 
-fn print_panic_info(pi: &PanicInfo, s: &Settings) -> Result<(), io::Error> {
-    writeln!(&s.out, "{}", style(&s.message).bold())?;
+fn print_panic_info(w: &mut impl Write, pi: &PanicInfo, s: &Settings) -> Result<(), io::Error> {
+    writeln!(w, "{}", s.color_scheme.header.apply_to(&s.message))?;
 
     let thread = std::thread::current();
     let thread_name = thread.name().unwrap_or("<unnamed>");
 
     // Print panic message.
-    let payload = pi
-        .payload()
-        .downcast_ref::<String>()
-        .map(String::as_str)
-        .or_else(|| pi.payload().downcast_ref::<&str>().cloned())
-        .unwrap_or("Box<Any>");
+    let payload = panic_payload(pi);
 
     for line in payload.lines() {
-        writeln!(&s.out, "  {}", style(line).yellow())?;
+        writeln!(w, "  {}", s.color_scheme.panic_message.apply_to(line))?;
     }
 
     // If known, print panic location.
-    write!(&s.out, "in ")?;
+    write!(w, "in ")?;
     if let Some(loc) = pi.location() {
         if s.lineno_suffix {
             writeln!(
-                &s.out,
+                w,
                 "{}:{}",
-                style(trim_filename(Path::new(loc.file()))).underlined(),
-                style(loc.line()).yellow()
+                s.color_scheme
+                    .filename
+                    .apply_to(trim_filename(Path::new(loc.file()))),
+                s.color_scheme.lineno.apply_to(loc.line())
             )?;
         } else {
             writeln!(
-                &s.out,
+                w,
                 "{}, line {}",
-                style(trim_filename(Path::new(loc.file()))).underlined(),
-                style(loc.line()).yellow()
+                s.color_scheme
+                    .filename
+                    .apply_to(trim_filename(Path::new(loc.file()))),
+                s.color_scheme.lineno.apply_to(loc.line())
             )?;
         }
     } else {
-        writeln!(&s.out, "<unknown>")?;
+        writeln!(w, "<unknown>")?;
     }
-    writeln!(&s.out, "thread: {}", style(thread_name).yellow())?;
+    writeln!(
+        w,
+        "thread: {}",
+        s.color_scheme.panic_message.apply_to(thread_name)
+    )?;
     Ok(())
 }
 
-fn print_backtrace_info(s: &Settings) -> Result<(), io::Error> {
+/// Prints the backtrace section of the panic output, returning the
+/// collected, filtered frames if a backtrace was actually resolved (i.e.
+/// `verbosity` was high enough), so callers don't have to resolve the
+/// native stack a second time for the JSON crash report.
+fn print_backtrace_info(w: &mut impl Write, s: &Settings) -> Result<Option<Vec<Frame>>, io::Error> {
+    // The installed panic handler honors `verbosity`, on-demand formatting
+    // of library-originated reports honors `lib_verbosity`.
+    let verbosity = if s.is_panic_handler {
+        s.verbosity
+    } else {
+        s.lib_verbosity
+    };
+    let var_name = if s.is_panic_handler {
+        "RUST_BACKTRACE"
+    } else {
+        "RUST_LIB_BACKTRACE"
+    };
+
     // Print some info on how to increase verbosity.
-    if s.verbosity == Verbosity::Minimal {
+    if verbosity == Verbosity::Minimal {
         writeln!(
-            &s.out,
-            "\nBacktrace omitted. Run with RUST_BACKTRACE=1 to display it."
+            w,
+            "\nBacktrace omitted. Run with {}=1 to display it.",
+            var_name
         )?;
     }
-    if s.verbosity <= Verbosity::Medium {
-        if s.verbosity == Verbosity::Medium {
+    if verbosity <= Verbosity::Medium {
+        if verbosity == Verbosity::Medium {
             // If exactly medium, no newline was printed before.
-            writeln!(&s.out)?;
+            writeln!(w)?;
         }
 
-        writeln!(
-            &s.out,
-            "Run with RUST_BACKTRACE=full to include source snippets."
-        )?;
+        writeln!(w, "Run with {}=full to include source snippets.", var_name)?;
     }
 
-    if s.verbosity >= Verbosity::Medium {
-        print_backtrace(None, s)?;
+    if verbosity >= Verbosity::Medium {
+        Ok(Some(print_backtrace(w, None, s, verbosity)?))
+    } else {
+        Ok(None)
     }
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn from_var_maps_full_to_full_verbosity() {
+        assert_eq!(Verbosity::from_var(Ok("full".to_owned())), Verbosity::Full);
+    }
+
+    #[test]
+    fn from_var_maps_zero_and_no_to_minimal() {
+        assert_eq!(Verbosity::from_var(Ok("0".to_owned())), Verbosity::Minimal);
+        assert_eq!(Verbosity::from_var(Ok("no".to_owned())), Verbosity::Minimal);
+        assert_eq!(Verbosity::from_var(Ok("NO".to_owned())), Verbosity::Minimal);
+    }
+
+    #[test]
+    fn from_var_maps_any_other_value_to_medium() {
+        assert_eq!(Verbosity::from_var(Ok("1".to_owned())), Verbosity::Medium);
+        assert_eq!(
+            Verbosity::from_var(Ok("anything".to_owned())),
+            Verbosity::Medium
+        );
+    }
+
+    #[test]
+    fn from_var_maps_unset_to_minimal() {
+        assert_eq!(
+            Verbosity::from_var(Err(std::env::VarError::NotPresent)),
+            Verbosity::Minimal
+        );
+    }
+
+    #[test]
+    fn json_escape_escapes_quotes_backslashes_and_control_chars() {
+        assert_eq!(json_escape("plain"), "plain");
+        assert_eq!(json_escape("a\"b"), "a\\\"b");
+        assert_eq!(json_escape("a\\b"), "a\\\\b");
+        assert_eq!(json_escape("a\nb\tc\rd"), "a\\nb\\tc\\rd");
+        assert_eq!(json_escape("\u{1}"), "\\u0001");
+    }
+
+    // `write_panic_report` only runs with a `&PanicInfo`, which can only be
+    // constructed by the runtime while a panic is in flight, so these tests
+    // install a temporary hook that calls it directly and stashes the
+    // result, then trigger a real (caught) panic to drive it.
+    static REPORT_TEST_GUARD: Mutex<()> = Mutex::new(());
+
+    fn run_write_panic_report(settings: Settings) -> io::Result<()> {
+        let _guard = REPORT_TEST_GUARD.lock().unwrap();
+        let result = Arc::new(Mutex::new(None));
+        let result_for_hook = Arc::clone(&result);
+        let prev_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |pi| {
+            *result_for_hook.lock().unwrap() = Some(write_panic_report(pi, &settings, None));
+        }));
+        let outcome = std::panic::catch_unwind(|| panic!("boom-for-report-test"));
+        std::panic::set_hook(prev_hook);
+        assert!(outcome.is_err());
+        let result = result.lock().unwrap().take().unwrap();
+        result
+    }
+
+    #[test]
+    fn write_panic_report_writes_expected_fields() {
+        let path = std::env::temp_dir().join(format!(
+            "better-panic-test-report-{:?}.json",
+            std::thread::current().id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        run_write_panic_report(Settings::new().report_path(&path)).unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert!(contents.contains("\"message\": \"boom-for-report-test\""));
+        assert!(contents.contains("\"target_os\""));
+        assert!(contents.contains("\"target_arch\""));
+        assert!(contents.contains("\"frames\""));
+    }
+
+    #[test]
+    fn write_panic_report_returns_err_for_unwritable_path() {
+        let err = run_write_panic_report(
+            Settings::new().report_path("/no/such/directory/better-panic-report.json"),
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+    }
 }